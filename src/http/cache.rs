@@ -0,0 +1,73 @@
+//!
+//! Bounded LRU caching of decrypted nodes and the memoized decryption pack for a node tree.
+//!
+//! Walking a node tree (as in the public-link example) or repeatedly resolving a node by handle
+//! otherwise redoes the same key derivation on every visit: `construct_tree_node` there
+//! recomputes [`crate::Client::decryption_pack`] on every recursive call, and each
+//! `get_decrypted_node_by_handle` re-derives the node's key. `NodeCache` lets [`ClientState`]
+//! remember both, so repeated traversals and attribute lookups become cache hits.
+//!
+
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+
+use lru::LruCache;
+
+use crate::{DecryptedNode, DecryptionPack};
+
+/// Default capacity, in entries, for a [`NodeCache`] built without an explicit capacity.
+pub(crate) const DEFAULT_CAPACITY: usize = 256;
+
+/// A bounded LRU cache of decrypted nodes, plus a memoized decryption pack for the node tree
+/// they came from.
+#[derive(Debug)]
+pub(crate) struct NodeCache {
+    nodes: Mutex<LruCache<String, Arc<DecryptedNode>>>,
+    decryption_pack: Mutex<Option<Arc<DecryptionPack>>>,
+}
+
+impl NodeCache {
+    /// Builds a cache holding at most `capacity` decrypted nodes. `capacity` of `0` is treated
+    /// as [`DEFAULT_CAPACITY`].
+    pub(crate) fn new(capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::new(DEFAULT_CAPACITY).unwrap());
+        Self {
+            nodes: Mutex::new(LruCache::new(capacity)),
+            decryption_pack: Mutex::new(None),
+        }
+    }
+
+    /// Returns a cached decrypted node for `handle`, if present, marking it most-recently-used.
+    pub(crate) fn get_node(&self, handle: &str) -> Option<Arc<DecryptedNode>> {
+        self.nodes.lock().unwrap().get(handle).cloned()
+    }
+
+    /// Inserts a freshly decrypted node into the cache, evicting the least-recently-used entry
+    /// if the cache is at capacity.
+    pub(crate) fn insert_node(&self, handle: String, node: Arc<DecryptedNode>) {
+        self.nodes.lock().unwrap().put(handle, node);
+    }
+
+    /// Returns the memoized decryption pack, if one has been computed since the cache was last
+    /// cleared.
+    pub(crate) fn decryption_pack(&self) -> Option<Arc<DecryptionPack>> {
+        self.decryption_pack.lock().unwrap().clone()
+    }
+
+    /// Memoizes `pack` as the current decryption pack.
+    pub(crate) fn set_decryption_pack(&self, pack: Arc<DecryptionPack>) {
+        *self.decryption_pack.lock().unwrap() = Some(pack);
+    }
+
+    /// Drops every cached node and the memoized decryption pack.
+    pub(crate) fn clear(&self) {
+        self.nodes.lock().unwrap().clear();
+        *self.decryption_pack.lock().unwrap() = None;
+    }
+}
+
+impl Default for NodeCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
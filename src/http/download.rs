@@ -0,0 +1,85 @@
+//!
+//! Multi-connection chunked downloads.
+//!
+//! MEGA's storage nodes serve plain HTTP(S) with `Range` support, so a single file can be fetched
+//! as several concurrent byte-range requests instead of one long-lived stream. This module splits
+//! a file into [`ClientState::download_concurrency`] roughly equal ranges, fetches them
+//! concurrently via [`HttpClient::get_range`], and reassembles the raw ciphertext in order into a
+//! single [`AsyncRead`].
+//!
+//! This module only reassembles ciphertext — it doesn't decrypt. Because MEGA's AES-CTR
+//! keystream is position-addressable, a decrypting reader layered on top (in the style of
+//! [`CheckpointingReader`](crate::http::CheckpointingReader)) could seed its counter from the
+//! byte offset and decrypt the reassembled stream in one pass; that reader doesn't exist yet.
+//!
+
+use std::pin::Pin;
+use std::sync::Arc;
+
+use futures::io::AsyncRead;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use std::io;
+use tokio_util::compat::FuturesAsyncReadCompatExt;
+use tokio_util::io::ReaderStream;
+use url::Url;
+
+use crate::error::Result;
+use crate::http::{ClientState, HttpClient};
+
+/// An inclusive byte range, `start..=end`.
+#[derive(Debug, Clone, Copy)]
+struct ByteRange {
+    start: u64,
+    end: u64,
+}
+
+fn split_ranges(file_size: u64, concurrency: usize) -> Vec<ByteRange> {
+    let concurrency = concurrency.max(1) as u64;
+    let chunk_size = file_size.div_ceil(concurrency).max(1);
+
+    let mut ranges = Vec::new();
+    let mut offset = 0;
+    while offset < file_size {
+        let end = (offset + chunk_size - 1).min(file_size - 1);
+        ranges.push(ByteRange { start: offset, end });
+        offset = end + 1;
+    }
+    ranges
+}
+
+/// Downloads `url`, splitting the transfer into [`ClientState::download_concurrency`] concurrent
+/// `Range` requests and reassembling them in order into a single [`AsyncRead`].
+///
+/// Falls back to a single [`HttpClient::get`] when `download_concurrency` is `1` or `file_size`
+/// is `0`.
+pub async fn download_ranged(
+    client: Arc<dyn HttpClient>,
+    state: &ClientState,
+    url: Url,
+    file_size: u64,
+) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+    if state.download_concurrency <= 1 || file_size == 0 {
+        return client.get(state, url).await;
+    }
+
+    let ranges = split_ranges(file_size, state.download_concurrency);
+
+    let range_streams = stream::iter(ranges.into_iter().map(|range| {
+        let client = Arc::clone(&client);
+        let url = url.clone();
+        async move {
+            let reader = client.get_range(state, url, range.start, range.end).await?;
+            Ok::<_, crate::error::Error>(ReaderStream::new(reader.compat()))
+        }
+    }))
+    // `buffered` preserves the input order of the stream even though the underlying futures
+    // may complete out of order, which is exactly the reassembly guarantee we need. Only the
+    // opened readers are held concurrently here — `try_flatten` below streams each range's
+    // bytes through as they arrive, so the whole file is never resident in memory at once.
+    .buffered(state.download_concurrency)
+    .map_err(|err| io::Error::new(io::ErrorKind::Other, err));
+
+    let chunks = range_streams.try_flatten().into_async_read();
+
+    Ok(Box::pin(chunks))
+}
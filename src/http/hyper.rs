@@ -0,0 +1,248 @@
+//!
+//! A [`HttpClient`] backend built directly on `hyper` + `hyper-util`, for consumers who don't
+//! want to pull in `reqwest`'s dependency tree.
+//!
+//! Enabled via the `hyper` cargo feature, independently of (or alongside) the default `reqwest`
+//! backend. `send_requests` delegates to [`crate::http::transport::send_requests_with_retry`] —
+//! the same 402/`X-Hashcash` challenge loop, exponential backoff, over-quota and `EAGAIN` retry
+//! algorithm the `reqwest` backend uses — via the [`RequestTransport`](crate::http::transport::RequestTransport)
+//! impl below.
+//!
+
+use std::io;
+use std::pin::Pin;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::io::AsyncRead;
+use futures::TryStreamExt;
+use http::{HeaderMap, StatusCode};
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Full, StreamBody};
+use hyper::body::Frame;
+use hyper::Request as HyperRequest;
+use hyper_rustls::HttpsConnector;
+use hyper_util::client::legacy::connect::{Connect, HttpConnector};
+use hyper_util::client::legacy::Client as LegacyClient;
+use hyper_util::rt::TokioExecutor;
+use tokio_util::compat::FuturesAsyncReadCompatExt;
+use tokio_util::io::ReaderStream;
+use url::Url;
+
+use crate::error::{Error, Result};
+use crate::http::quota::{ensure_not_over_quota, throttled};
+use crate::http::transport::{send_requests_with_retry, RequestTransport};
+use crate::http::HttpClient;
+use crate::protocol::commands::{Request, Response};
+use crate::ClientState;
+
+/// The request body type used for every request `HyperClient` sends.
+///
+/// The `/cs` and GET paths only ever need a single in-memory buffer, but uploads stream their
+/// body from an `AsyncRead` via [`StreamBody`], which is a different concrete body type. Boxing
+/// every request body to this common type lets `LegacyClient` stay generic over one body type
+/// instead of rejecting the upload path with a type mismatch.
+type Body = BoxBody<Bytes, io::Error>;
+
+fn full_body(bytes: Bytes) -> Body {
+    Full::new(bytes).map_err(|never: std::convert::Infallible| match never {}).boxed()
+}
+
+/// A [`HttpClient`] implementation built on `hyper` + `hyper-util`, instead of `reqwest`.
+#[derive(Debug, Clone)]
+pub struct HyperClient<C = HttpsConnector<HttpConnector>> {
+    inner: LegacyClient<C, Body>,
+}
+
+impl HyperClient<HttpsConnector<HttpConnector>> {
+    /// Builds a `HyperClient` using the platform's native TLS roots.
+    ///
+    /// Returns `Err` rather than panicking if the platform's native TLS roots can't be loaded.
+    pub fn new() -> Result<Self> {
+        let connector = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_native_roots()
+            .map_err(Error::from)?
+            .https_or_http()
+            .enable_http1()
+            .build();
+
+        Ok(Self::from_connector(connector))
+    }
+}
+
+impl<C> HyperClient<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    /// Builds a `HyperClient` from a caller-supplied connector, for callers who need custom TLS
+    /// or proxy behavior.
+    pub fn from_connector(connector: C) -> Self {
+        Self {
+            inner: LegacyClient::builder(TokioExecutor::new()).build(connector),
+        }
+    }
+
+    async fn get_with_range_header(
+        &self,
+        state: &ClientState,
+        url: Url,
+        range: Option<String>,
+    ) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+        let mut builder = HyperRequest::builder().method("GET").uri(url.as_str());
+        if let Some(range) = range {
+            builder = builder.header("range", range);
+        }
+        let request = builder.body(full_body(Bytes::new())).map_err(Error::from)?;
+
+        let response = self.inner.request(request).await.map_err(Error::from)?;
+        ensure_not_over_quota(response.status(), response.headers())?;
+        if !response.status().is_success() {
+            return Err(Error::UnexpectedHttpStatus(response.status()));
+        }
+
+        let stream = response
+            .into_body()
+            .into_data_stream()
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err));
+
+        Ok(Box::pin(throttled(stream, state.transfer_rate_limiter.clone()).into_async_read()))
+    }
+
+    async fn post_with_offset(
+        &self,
+        state: &ClientState,
+        mut url: Url,
+        body: Pin<Box<dyn AsyncRead + Send + Sync>>,
+        content_length: Option<u64>,
+        offset: Option<u64>,
+    ) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+        if let Some(offset) = offset {
+            // `Url::join` resolves a relative reference, which *replaces* the last path segment
+            // (the upload handle) instead of appending to it. Push the offset as an additional
+            // segment so `.../ul/EAADxyz` becomes `.../ul/EAADxyz/{offset}`.
+            url.path_segments_mut()
+                .expect("MEGA upload URLs are always base URLs")
+                .push(&offset.to_string());
+        }
+
+        let stream = throttled(ReaderStream::new(body.compat()), state.transfer_rate_limiter.clone()).map_ok(Frame::data);
+        let body: Body = StreamBody::new(stream).boxed();
+
+        let mut builder = HyperRequest::builder().method("POST").uri(url.as_str());
+        if let Some(content_length) = content_length {
+            builder = builder.header("content-length", content_length);
+        }
+        let request = builder.body(body).map_err(Error::from)?;
+
+        let response = self.inner.request(request).await.map_err(Error::from)?;
+        ensure_not_over_quota(response.status(), response.headers())?;
+        if !response.status().is_success() {
+            return Err(Error::UnexpectedHttpStatus(response.status()));
+        }
+
+        let stream = response
+            .into_body()
+            .into_data_stream()
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err));
+
+        Ok(Box::pin(stream.into_async_read()))
+    }
+}
+
+#[async_trait]
+impl<C> RequestTransport for HyperClient<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    async fn send_cs_request(&self, url: &Url, body: &str, hashcash_header: Option<&str>, timeout: Option<Duration>) -> Option<(StatusCode, HeaderMap, Bytes)> {
+        let mut builder = HyperRequest::builder().method("POST").uri(url.as_str()).header("content-type", "application/json");
+        if let Some(header_value) = hashcash_header {
+            builder = builder.header("x-hashcash", header_value);
+        }
+
+        let request = match builder.body(full_body(Bytes::from(body.to_string()))) {
+            Ok(request) => request,
+            Err(e) => {
+                tracing::error!(?e, "failed to build MEGA request");
+                return None;
+            }
+        };
+
+        let request_fut = self.inner.request(request);
+
+        let response = match if let Some(timeout) = timeout {
+            tokio::time::timeout(timeout, request_fut).await
+        } else {
+            Ok(request_fut.await)
+        } {
+            Ok(Ok(r)) => r,
+            Ok(Err(e)) => {
+                tracing::error!(?e, "network error while making MEGA request");
+                return None;
+            }
+            Err(e) => {
+                tracing::error!(?e, "timeout while making MEGA request");
+                return None;
+            }
+        };
+
+        let status = response.status();
+        let headers = response.headers().clone();
+
+        let body = match response.into_body().collect().await {
+            Ok(collected) => collected.to_bytes(),
+            Err(e) => {
+                tracing::error!(?e, "failed to read MEGA response body");
+                return None;
+            }
+        };
+
+        Some((status, headers, body))
+    }
+}
+
+#[async_trait]
+impl<C> HttpClient for HyperClient<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    #[tracing::instrument(skip(self, state, query_params))]
+    async fn send_requests(&self, state: &ClientState, requests: &[Request], query_params: &[(&str, &str)]) -> Result<Vec<Response>> {
+        tracing::trace!(?state, "preparing MEGA request");
+        send_requests_with_retry(self, state, requests, query_params).await
+    }
+
+    async fn get(&self, state: &ClientState, url: Url) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+        self.get_with_range_header(state, url, None).await
+    }
+
+    async fn get_range(&self, state: &ClientState, url: Url, start: u64, end: u64) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+        self.get_with_range_header(state, url, Some(format!("bytes={start}-{end}"))).await
+    }
+
+    async fn get_from(&self, state: &ClientState, url: Url, offset: u64) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+        self.get_with_range_header(state, url, Some(format!("bytes={offset}-"))).await
+    }
+
+    async fn post(
+        &self,
+        state: &ClientState,
+        url: Url,
+        body: Pin<Box<dyn AsyncRead + Send + Sync>>,
+        content_length: Option<u64>,
+    ) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+        self.post_with_offset(state, url, body, content_length, None).await
+    }
+
+    async fn post_from(
+        &self,
+        state: &ClientState,
+        url: Url,
+        body: Pin<Box<dyn AsyncRead + Send + Sync>>,
+        content_length: Option<u64>,
+        offset: u64,
+    ) -> Result<Pin<Box<dyn AsyncRead + Send>>> {
+        self.post_with_offset(state, url, body, content_length, Some(offset)).await
+    }
+}
@@ -0,0 +1,198 @@
+//!
+//! Merkle-tree integrity manifest for verifiable and repairable downloads.
+//!
+//! A download is partitioned into fixed-size plaintext leaves, each hashed with SHA3-256; the
+//! tree is then built bottom-up, promoting a lone node unchanged whenever a level has an odd
+//! count. This lets a corrupted transfer be detected and repaired leaf-by-leaf — via
+//! [`MerkleManifest::failing_leaves`] and the Range-download layer — instead of discarding and
+//! re-fetching the whole file.
+//!
+
+use sha3::{Digest, Sha3_256};
+
+/// The default leaf size used when none is specified: 1 MiB.
+pub const DEFAULT_LEAF_SIZE: u64 = 1024 * 1024;
+
+fn hash_leaf(bytes: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha3_256::new();
+    hasher.update(bytes);
+    hasher.finalize().into()
+}
+
+fn hash_pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = Sha3_256::new();
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+fn build_levels(leaves: Vec<[u8; 32]>) -> Vec<Vec<[u8; 32]>> {
+    let mut levels = vec![leaves];
+
+    while levels.last().unwrap().len() > 1 {
+        let prev = levels.last().unwrap();
+        let mut next = Vec::with_capacity(prev.len().div_ceil(2));
+
+        let mut i = 0;
+        while i < prev.len() {
+            if i + 1 < prev.len() {
+                next.push(hash_pair(&prev[i], &prev[i + 1]));
+            } else {
+                // Odd node out: promote it unchanged to the next level.
+                next.push(prev[i]);
+            }
+            i += 2;
+        }
+
+        levels.push(next);
+    }
+
+    levels
+}
+
+/// The root digest of an empty tree (zero leaves), e.g. for a 0-byte download.
+const EMPTY_ROOT: [u8; 32] = [0; 32];
+
+/// Returns the root digest of `levels`, or [`EMPTY_ROOT`] if there are no leaves at all.
+///
+/// `build_levels` always returns at least one (possibly empty) level, so the empty case has to
+/// be handled explicitly rather than indexing into it.
+fn root_of(levels: &[Vec<[u8; 32]>]) -> [u8; 32] {
+    match levels.last() {
+        Some(level) if !level.is_empty() => level[0],
+        _ => EMPTY_ROOT,
+    }
+}
+
+/// A binary Merkle tree over a download's fixed-size plaintext leaves.
+///
+/// Build one incrementally while streaming via [`MerkleBuilder`], or all at once via
+/// [`MerkleManifest::from_leaves`].
+#[derive(Debug, Clone)]
+pub struct MerkleManifest {
+    /// The 32-byte root digest of the tree.
+    pub root: [u8; 32],
+    /// The size, in bytes, of every leaf except possibly the last.
+    pub leaf_size: u64,
+    /// The number of leaves the plaintext was partitioned into.
+    pub leaf_count: u64,
+    levels: Vec<Vec<[u8; 32]>>,
+}
+
+impl MerkleManifest {
+    /// Builds a manifest over `leaves`, hashing each with SHA3-256. The final leaf may be
+    /// shorter than `leaf_size`; it's hashed over its real length.
+    pub fn from_leaves(leaf_size: u64, leaves: &[Vec<u8>]) -> Self {
+        let leaf_hashes: Vec<[u8; 32]> = leaves.iter().map(|leaf| hash_leaf(leaf)).collect();
+        let leaf_count = leaf_hashes.len() as u64;
+        let levels = build_levels(leaf_hashes);
+        let root = root_of(&levels);
+
+        Self { root, leaf_size, leaf_count, levels }
+    }
+
+    /// Returns the sibling hashes along the path from `leaf_index` to the root, each paired
+    /// with whether the sibling sits to the left (`true`) or right (`false`) of the node on the
+    /// path. `None` if `leaf_index` is out of range.
+    pub fn proof(&self, leaf_index: u64) -> Option<Vec<([u8; 32], bool)>> {
+        if leaf_index >= self.leaf_count {
+            return None;
+        }
+
+        let mut index = leaf_index as usize;
+        let mut path = Vec::new();
+
+        for level in &self.levels[..self.levels.len() - 1] {
+            if index % 2 == 1 {
+                path.push((level[index - 1], true));
+            } else if index + 1 < level.len() {
+                path.push((level[index + 1], false));
+            }
+            // If neither arm matched, `index` was the odd node out, promoted unchanged — no
+            // sibling to record at this level.
+            index /= 2;
+        }
+
+        Some(path)
+    }
+
+    /// Recomputes the root from `leaf_bytes` and `proof`, returning whether it matches `root`.
+    pub fn verify(leaf_bytes: &[u8], proof: &[([u8; 32], bool)], root: &[u8; 32]) -> bool {
+        let mut hash = hash_leaf(leaf_bytes);
+        for (sibling, is_left) in proof {
+            hash = if *is_left { hash_pair(sibling, &hash) } else { hash_pair(&hash, sibling) };
+        }
+        &hash == root
+    }
+
+    /// Checks each `(leaf_index, leaf_bytes)` pair against this manifest's recorded leaf hash,
+    /// returning the indices that don't match (or are out of range).
+    ///
+    /// Mapping the returned indices through [`MerkleManifest::leaf_byte_range`] gives exactly the
+    /// byte ranges to re-request after a corrupted transfer, instead of re-fetching the whole file.
+    pub fn failing_leaves<'a>(&self, leaves: impl IntoIterator<Item = (u64, &'a [u8])>) -> Vec<u64> {
+        leaves
+            .into_iter()
+            .filter(|(index, bytes)| {
+                self.levels[0]
+                    .get(*index as usize)
+                    .map(|expected| *expected != hash_leaf(bytes))
+                    .unwrap_or(true)
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Returns the inclusive byte range `leaf_index` occupies in a plaintext of `file_size`
+    /// bytes, accounting for the final leaf being shorter than `leaf_size`.
+    pub fn leaf_byte_range(&self, leaf_index: u64, file_size: u64) -> (u64, u64) {
+        let start = leaf_index * self.leaf_size;
+        let end = (start + self.leaf_size).min(file_size).saturating_sub(1);
+        (start, end)
+    }
+}
+
+/// Incrementally builds a [`MerkleManifest`] as a download streams past, one completed leaf at
+/// a time.
+pub struct MerkleBuilder {
+    leaf_size: u64,
+    buffer: Vec<u8>,
+    leaves: Vec<[u8; 32]>,
+}
+
+impl MerkleBuilder {
+    /// Creates a builder that hashes leaves of `leaf_size` bytes.
+    pub fn new(leaf_size: u64) -> Self {
+        Self { leaf_size, buffer: Vec::new(), leaves: Vec::new() }
+    }
+
+    /// Feeds the next chunk of plaintext bytes in, hashing and recording any leaf it completes.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+
+        while self.buffer.len() as u64 >= self.leaf_size {
+            let leaf: Vec<u8> = self.buffer.drain(..self.leaf_size as usize).collect();
+            self.leaves.push(hash_leaf(&leaf));
+        }
+    }
+
+    /// Finalizes the manifest. A shorter trailing leaf (the final leaf, if the plaintext length
+    /// isn't a multiple of `leaf_size`) is hashed over its real length.
+    pub fn finish(mut self) -> MerkleManifest {
+        if !self.buffer.is_empty() {
+            self.leaves.push(hash_leaf(&self.buffer));
+        }
+
+        let leaf_count = self.leaves.len() as u64;
+        let levels = build_levels(self.leaves);
+        let root = root_of(&levels);
+
+        MerkleManifest { root, leaf_size: self.leaf_size, leaf_count, levels }
+    }
+}
+
+impl Default for MerkleBuilder {
+    fn default() -> Self {
+        Self::new(DEFAULT_LEAF_SIZE)
+    }
+}
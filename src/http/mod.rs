@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::pin::Pin;
 use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
 use std::time::Duration;
 
 use async_trait::async_trait;
@@ -11,6 +12,23 @@ use zeroize::Zeroize;
 
 #[cfg(feature = "reqwest")]
 mod reqwest;
+#[cfg(feature = "hyper")]
+mod hyper;
+mod cache;
+mod download;
+mod merkle;
+mod quota;
+mod ratelimit;
+mod resume;
+mod transport;
+
+pub(crate) use cache::NodeCache;
+pub use download::download_ranged;
+pub use merkle::{MerkleBuilder, MerkleManifest};
+pub(crate) use ratelimit::RateLimiter;
+pub use resume::{CheckpointingReader, ResumeToken};
+#[cfg(feature = "hyper")]
+pub use hyper::HyperClient;
 
 use crate::{DecryptionContext, utils};
 use crate::error::Result;
@@ -92,8 +110,35 @@ pub struct ClientState {
     pub(crate) https: bool,
     /// The request counter, for idempotency.
     pub(crate) id_counter: AtomicU64,
+    /// The number of concurrent range requests to use when downloading a file.
+    ///
+    /// Defaults to `1`, which preserves the historical single-connection behavior. Values
+    /// greater than `1` split the download into that many byte ranges and fetch them over
+    /// separate connections via [`HttpClient::get_range`], reassembling them in order.
+    pub(crate) download_concurrency: usize,
     /// The user's session.
     pub(crate) session: Option<SecretBox<UserSession>>,
+    /// Bounded cache of already-decrypted nodes and the memoized decryption pack for the current
+    /// node tree, to avoid redundant crypto work when the same tree is walked or queried
+    /// repeatedly.
+    pub(crate) cache: NodeCache,
+    /// Throttles `/cs` API requests to a configured requests/sec, proactively staying under
+    /// MEGA's rate limit instead of retrying `EOVERQUOTA` responses.
+    pub(crate) request_rate_limiter: Option<Arc<RateLimiter>>,
+    /// Throttles file transfer bytes to a configured bytes/sec, proactively staying under
+    /// MEGA's bandwidth quota.
+    pub(crate) transfer_rate_limiter: Option<Arc<RateLimiter>>,
+}
+
+impl ClientState {
+    /// Drops every cached decrypted node and the memoized decryption pack.
+    ///
+    /// Exposed publicly as `Client::clear_cache`; callers who mutate the account out-of-band
+    /// (e.g. another client moving or deleting nodes) should call this so subsequent lookups
+    /// reflect the change instead of a stale cache entry.
+    pub(crate) fn clear_cache(&self) {
+        self.cache.clear();
+    }
 }
 
 #[async_trait]
@@ -107,13 +152,46 @@ pub trait HttpClient: Send + Sync {
     ) -> Result<Vec<Response>>;
 
     /// Initiates a simple GET request, returning the response body as a reader.
-    async fn get(&self, url: Url) -> Result<Pin<Box<dyn AsyncRead + Send>>>;
+    ///
+    /// `state` is consulted for `transfer_rate_limiter` throttling and 509/over-quota handling,
+    /// matching [`HttpClient::send_requests`].
+    async fn get(&self, state: &ClientState, url: Url) -> Result<Pin<Box<dyn AsyncRead + Send>>>;
+
+    /// Initiates a GET request for the inclusive byte range `start..=end`, returning the
+    /// response body as a reader.
+    ///
+    /// Implementations send this as an HTTP `Range: bytes={start}-{end}` request. Callers are
+    /// expected to only use this against servers (such as MEGA's storage nodes) that are known
+    /// to support range requests.
+    async fn get_range(&self, state: &ClientState, url: Url, start: u64, end: u64) -> Result<Pin<Box<dyn AsyncRead + Send>>>;
+
+    /// Initiates a GET request resuming from `offset`, returning the response body as a reader.
+    ///
+    /// Implementations send this as an open-ended `Range: bytes={offset}-` request. Used to
+    /// resume a download from a [`ResumeToken`] after a crash or network drop.
+    async fn get_from(&self, state: &ClientState, url: Url, offset: u64) -> Result<Pin<Box<dyn AsyncRead + Send>>>;
 
     /// Initiates a simple POST request, with body and optional `content-length`, returning the response body as a reader.
     async fn post(
         &self,
+        state: &ClientState,
+        url: Url,
+        body: Pin<Box<dyn AsyncRead + Send + Sync>>,
+        content_length: Option<u64>,
+    ) -> Result<Pin<Box<dyn AsyncRead + Send>>>;
+
+    /// Initiates a POST request uploading `body`, resuming an upload whose first `offset` bytes
+    /// were already accepted by the server.
+    ///
+    /// MEGA upload targets accept the already-uploaded byte offset as a trailing URL path
+    /// segment, so implementations append `offset` to `url` as an extra path segment rather than
+    /// using a `Range` header.
+    async fn post_from(
+        &self,
+        state: &ClientState,
         url: Url,
         body: Pin<Box<dyn AsyncRead + Send + Sync>>,
         content_length: Option<u64>,
+        offset: u64,
     ) -> Result<Pin<Box<dyn AsyncRead + Send>>>;
 }
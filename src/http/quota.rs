@@ -0,0 +1,62 @@
+//!
+//! Shared over-quota detection and transfer throttling, used by every [`HttpClient`] backend.
+//!
+//! `reqwest` and `hyper` both build their request/response types on top of the `http` crate, so
+//! the status/header inspection here is written once against `http::StatusCode`/`http::HeaderMap`
+//! instead of being duplicated per backend.
+//!
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::{Stream, TryStreamExt};
+use http::{HeaderMap, StatusCode};
+
+use crate::error::{Error, Result};
+use crate::http::RateLimiter;
+
+/// MEGA's over-quota / `EOVERQUOTA` HTTP status. Not a standard status code, so `http`'s
+/// `StatusCode` has no named constant for it.
+pub(crate) const OVER_QUOTA_STATUS: u16 = 509;
+
+/// The backoff to use when a 509 response doesn't include a usable `retry-after` hint.
+pub(crate) const DEFAULT_OVER_QUOTA_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Extracts the server-provided backoff hint from a 509 response's headers, falling back to
+/// [`DEFAULT_OVER_QUOTA_BACKOFF`] if it's missing or unparseable.
+pub(crate) fn over_quota_retry_after(headers: &HeaderMap) -> Duration {
+    headers
+        .get("retry-after")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_OVER_QUOTA_BACKOFF)
+}
+
+/// Returns `Err(Error::OverQuota { .. })` if `status`/`headers` describe a 509 over-quota
+/// response.
+pub(crate) fn ensure_not_over_quota(status: StatusCode, headers: &HeaderMap) -> Result<()> {
+    if status.as_u16() == OVER_QUOTA_STATUS {
+        return Err(Error::OverQuota { retry_after: over_quota_retry_after(headers) });
+    }
+    Ok(())
+}
+
+/// Wraps `stream`, awaiting `limiter.acquire` for each item's byte length before yielding it, so
+/// transfer bandwidth stays under the configured `transfer_rate_limiter`. A no-op when `limiter`
+/// is `None`.
+pub(crate) fn throttled<S, B>(stream: S, limiter: Option<Arc<RateLimiter>>) -> impl Stream<Item = std::result::Result<B, std::io::Error>>
+where
+    S: Stream<Item = std::result::Result<B, std::io::Error>>,
+    B: AsRef<[u8]>,
+{
+    stream.and_then(move |item| {
+        let limiter = limiter.clone();
+        async move {
+            if let Some(limiter) = limiter {
+                limiter.acquire(item.as_ref().len() as u64).await;
+            }
+            Ok(item)
+        }
+    })
+}
@@ -0,0 +1,88 @@
+//!
+//! Token-bucket rate limiting for the `/cs` API and file transfers.
+//!
+//! MEGA enforces both a request rate and a transfer bandwidth quota, and returns a 509-style
+//! over-quota response (or, on `/cs`, an `EOVERQUOTA` error code) once a client exceeds them.
+//! `RateLimiter` lets [`ClientState`](crate::http::ClientState) throttle itself proactively so
+//! long-running sync tools stay under those limits instead of tripping a ban and burning retry
+//! attempts on responses that were always going to fail.
+//!
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A token bucket: tokens refill continuously at `rate_per_sec`, up to `capacity`, and
+/// [`RateLimiter::acquire`] sleeps just long enough for enough tokens to accumulate.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    rate_per_sec: f64,
+    capacity: f64,
+    state: Mutex<BucketState>,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    /// Creates a limiter that allows `rate_per_sec` units/sec on average, with bursts up to
+    /// `rate_per_sec` units before throttling kicks in.
+    pub(crate) fn new(rate_per_sec: f64) -> Self {
+        Self {
+            rate_per_sec,
+            capacity: rate_per_sec,
+            state: Mutex::new(BucketState { tokens: rate_per_sec, last_refill: Instant::now() }),
+        }
+    }
+
+    /// Waits until `amount` tokens are available, then consumes them.
+    ///
+    /// `amount` may exceed `capacity` (a single `reqwest`/`hyper` body chunk routinely exceeds a
+    /// modest bytes/sec rate) — in that case this waits for the bucket to fill completely, then
+    /// spends it all at once, leaving the bucket in debt so the next `acquire` waits longer
+    /// accordingly, rather than waiting forever for a token count the bucket can never hold.
+    pub(crate) async fn acquire(&self, amount: u64) {
+        let amount = amount as f64;
+        let threshold = amount.min(self.capacity);
+
+        loop {
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+
+                let now = Instant::now();
+                let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+                state.tokens = (state.tokens + elapsed * self.rate_per_sec).min(self.capacity);
+                state.last_refill = now;
+
+                if state.tokens >= threshold {
+                    state.tokens -= amount;
+                    None
+                } else {
+                    let deficit = threshold - state.tokens;
+                    Some(Duration::from_secs_f64(deficit / self.rate_per_sec))
+                }
+            };
+
+            match wait {
+                None => return,
+                Some(duration) => tokio::time::sleep(duration).await,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn acquire_larger_than_capacity_does_not_hang() {
+        let limiter = RateLimiter::new(10.0);
+
+        // Exceeds `capacity` (10): must wait for the bucket to fill, then proceed instead of
+        // looping forever waiting for a token count the bucket can never hold.
+        tokio::time::timeout(Duration::from_secs(5), limiter.acquire(25)).await.expect("acquire should not hang");
+    }
+}
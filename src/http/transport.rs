@@ -0,0 +1,163 @@
+//!
+//! The shared MEGA `/cs` request algorithm: URL/session-query construction, exponential backoff,
+//! the 402/X-Hashcash challenge loop, proactive request-rate throttling, 509/over-quota backoff,
+//! and `EAGAIN` retries.
+//!
+//! Every [`HttpClient`](crate::http::HttpClient) backend implements only [`RequestTransport`] — a
+//! single "send these bytes, get back a status/headers/body" method — and delegates
+//! `send_requests` to [`send_requests_with_retry`], so this algorithm is written once instead of
+//! once per backend.
+//!
+
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use http::{HeaderMap, StatusCode};
+use secrecy::ExposeSecret;
+use url::Url;
+
+use crate::error::{Error, Result};
+use crate::http::quota::ensure_not_over_quota;
+use crate::http::ClientState;
+use crate::protocol::commands::{Request, Response};
+use crate::utils::hashcash::{gencash, parse_hashcash_header};
+use crate::ErrorCode;
+
+/// Sends a single `/cs` POST attempt, honoring `timeout`, and reports back the raw outcome.
+///
+/// Implementations only need to build and send the request; every retry decision (hashcash,
+/// backoff, over-quota, `EAGAIN`) is made by [`send_requests_with_retry`] from the returned
+/// status/headers/body. Returns `None` on a network error or timeout, which
+/// [`send_requests_with_retry`] treats as an immediate retry, matching the existing backoff
+/// schedule.
+#[async_trait]
+pub(crate) trait RequestTransport: Send + Sync {
+    async fn send_cs_request(&self, url: &Url, body: &str, hashcash_header: Option<&str>, timeout: Option<Duration>) -> Option<(StatusCode, HeaderMap, Bytes)>;
+}
+
+/// Runs the shared MEGA `/cs` retry algorithm against `transport`.
+pub(crate) async fn send_requests_with_retry(transport: &dyn RequestTransport, state: &ClientState, requests: &[Request], query_params: &[(&str, &str)]) -> Result<Vec<Response>> {
+    let url = {
+        let mut url = state.origin.join("/cs")?;
+
+        let mut qs = url.query_pairs_mut();
+        let id_counter = state.id_counter.fetch_add(1, Ordering::SeqCst);
+        qs.append_pair("id", id_counter.to_string().as_str());
+
+        if let Some(session) = state.session.as_ref() {
+            qs.append_pair("sid", session.expose_secret().session_id.as_str());
+        }
+
+        qs.extend_pairs(query_params);
+
+        qs.finish();
+        drop(qs);
+
+        url
+    };
+
+    let body = json::to_string(&requests).unwrap();
+
+    let mut delay = state.min_retry_delay;
+    let mut hashcash_challenge: Option<(String, u8)> = None;
+    let mut last_over_quota: Option<Duration> = None;
+
+    let mut attempt = 0;
+    while attempt < state.max_retries {
+        attempt += 1;
+        if attempt > 1 && hashcash_challenge.is_none() {
+            tracing::debug!(?delay, "sleeping for exponential back‑off before retrying");
+            tokio::time::sleep(delay).await;
+            delay = std::cmp::min(delay * 2, state.max_retry_delay);
+        }
+
+        if let Some(limiter) = state.request_rate_limiter.as_ref() {
+            limiter.acquire(1).await;
+        }
+
+        let mut hashcash_header = None;
+        if let Some((ref token, easiness)) = hashcash_challenge {
+            // Use a blocking worker to generate the hashcash stamp. This allows the CPU to
+            // be used more efficiently, instead of blocking the tokio runtime.
+            let stamp = tokio::task::spawn_blocking({
+                let token = token.clone();
+                move || gencash(&token, easiness)
+            })
+            .await
+            .expect("hashcash worker panicked");
+            let header_value = format!("1:{token}:{stamp}");
+            tracing::trace!(header=%header_value, "attached solved X‑Hashcash header");
+            hashcash_header = Some(header_value);
+            hashcash_challenge = None;
+        }
+
+        tracing::info!(json = %body, "Sending request");
+
+        let Some((status, headers, response_bytes)) = transport.send_cs_request(&url, &body, hashcash_header.as_deref(), state.timeout).await else {
+            continue;
+        };
+
+        let status_str = status.to_string();
+
+        // ─────────────────────────────────────────────────────────────────────
+        // 402 = Payment‑Required → the server is challenging us with Hashcash
+        // ─────────────────────────────────────────────────────────────────────
+        if status == StatusCode::PAYMENT_REQUIRED {
+            tracing::debug!("received 402 – server requests Hashcash proof‑of‑work");
+
+            if let Some((token, easiness)) = headers.get("x-hashcash").and_then(parse_hashcash_header) {
+                hashcash_challenge = Some((token.clone(), easiness));
+                tracing::trace!(token = %token, easiness, "parsed Hashcash challenge");
+                continue;
+            }
+
+            tracing::error!("402 received but no valid Hashcash challenge found — aborting");
+            return Err(Error::MaxRetriesReached);
+        }
+
+        // ─────────────────────────────────────────────────────────────────────
+        // 509 = over quota → sleep for the server-provided hint and retry, rather than
+        // burning a retry attempt on a request that was always going to fail
+        // ─────────────────────────────────────────────────────────────────────
+        if let Err(Error::OverQuota { retry_after }) = ensure_not_over_quota(status, &headers) {
+            tracing::warn!(?retry_after, "received 509 – over quota, sleeping before retrying");
+            last_over_quota = Some(retry_after);
+            tokio::time::sleep(retry_after).await;
+            attempt -= 1;
+            continue;
+        }
+
+        // ─────────────────────────────────────────────────────────────────────
+        // The response did not ask for Hashcash – handle as usual
+        // ─────────────────────────────────────────────────────────────────────
+        if status.is_client_error() || status.is_server_error() {
+            tracing::error!(status = %status_str, "HTTP error status, will retry");
+            continue;
+        }
+
+        tracing::info!(status = %status_str, body = %String::from_utf8_lossy(&response_bytes), "Response");
+
+        if let Ok(code) = json::from_slice::<ErrorCode>(&response_bytes) {
+            if code == ErrorCode::EAGAIN {
+                tracing::debug!(?code, "MEGA returned error code EAGAIN (request failed but may be retried)");
+                continue;
+            }
+            if code != ErrorCode::OK {
+                tracing::error!(?code, "MEGA error code");
+            }
+            return Err(Error::from(code));
+        }
+
+        let responses: Vec<json::Value> = json::from_slice(&response_bytes).map_err(|e| {
+            tracing::error!(?e, "could not deserialize MEGA response array");
+            e
+        })?;
+
+        return requests.iter().zip(responses).map(|(req, resp)| req.parse_response_data(resp)).collect();
+    }
+
+    tracing::error!("maximum retries reached, cancelling MEGA request");
+    Err(last_over_quota.map(|retry_after| Error::OverQuota { retry_after }).unwrap_or(Error::MaxRetriesReached))
+}